@@ -1,22 +1,59 @@
-use tauri::command;
+use crate::cmd::mcp_server::McpState;
+use serde::Serialize;
 use serde_json::Value;
+use tauri::State;
+use uuid::Uuid;
 
-#[command]
-pub async fn mcp_read_resource(uri: String) -> Result<Value, String> {
-    // This will be called from Rust HTTP handler and forwarded to frontend
-    // The frontend should have registered a listener for this
-    Ok(serde_json::json!({
-        "uri": uri,
-        "action": "read_resource"
-    }))
+/// Called by the frontend's `window.__mcpServer` bridge once it has produced a result for a
+/// pending request, completing the oneshot channel that `McpState` is awaiting inside the
+/// matching HTTP handler.
+#[tauri::command]
+pub fn mcp_respond(id: String, result: Value, state: State<McpState>) -> Result<(), String> {
+    let id = Uuid::parse_str(&id).map_err(|e| format!("Invalid request id: {e}"))?;
+    if state.complete_pending(id, result) {
+        Ok(())
+    } else {
+        Err(format!("No pending MCP request with id {id}"))
+    }
 }
 
-#[command]
-pub async fn mcp_call_tool(name: String, args: Value) -> Result<Value, String> {
-    // This will be called from Rust HTTP handler and forwarded to frontend
-    Ok(serde_json::json!({
-        "tool": name,
-        "args": args,
-        "action": "call_tool"
-    }))
+#[derive(Serialize)]
+pub struct McpServerInfo {
+    pub bind_addr: String,
+    pub token: Option<String>,
+}
+
+/// Read the MCP server's current bind address and bearer token, so the settings UI can display
+/// them to the user.
+#[tauri::command]
+pub async fn mcp_get_server_info(state: State<'_, McpState>) -> Result<McpServerInfo, String> {
+    let config = state.config().await;
+    Ok(McpServerInfo {
+        bind_addr: config.bind_addr.to_string(),
+        token: config.token,
+    })
+}
+
+/// Roll the bearer token, invalidating any agent that was using the old one.
+#[tauri::command]
+pub async fn mcp_regenerate_token(state: State<'_, McpState>) -> Result<String, String> {
+    Ok(state.regenerate_token().await)
+}
+
+/// Change the bind address the MCP server listens on, rebinding the listener immediately.
+#[tauri::command]
+pub async fn mcp_set_bind_addr(addr: String, state: State<'_, McpState>) -> Result<(), String> {
+    let bind_addr = addr
+        .parse()
+        .map_err(|e| format!("Invalid bind address: {e}"))?;
+    state
+        .set_bind_addr(bind_addr)
+        .await
+        .map_err(|e| format!("Failed to bind to {addr}: {e}"))
+}
+
+/// Clear the MCP audit log, e.g. once the user has reviewed a batch of agent activity.
+#[tauri::command]
+pub fn mcp_clear_log(state: State<McpState>) {
+    state.clear_log();
 }
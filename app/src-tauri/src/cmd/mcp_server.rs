@@ -1,82 +1,538 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Json, Response,
+    },
     routing::{get, post},
     Router,
 };
+use dashmap::DashMap;
+use futures::stream::Stream;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::sync::Arc;
-use tauri::{AppHandle, Manager, Wry};
-use tower_http::cors::{Any, CorsLayer};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{broadcast, oneshot, RwLock};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use tower_http::cors::CorsLayer;
+use uuid::Uuid;
+
+/// How long we wait for `window.__mcpServer` to answer a forwarded request before giving up.
+const FRONTEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Capacity of the SSE notification channel; slow subscribers simply miss the oldest events.
+const NOTIFICATION_CHANNEL_CAPACITY: usize = 256;
+
+/// How many entries the in-memory audit log keeps before evicting the oldest.
+const MCP_LOG_CAPACITY: usize = 200;
+
+const MCP_PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Runtime-configurable MCP server settings. The bearer token gates every route and is persisted
+/// to disk so it survives app restarts. Changing `bind_addr` via `McpState::set_bind_addr`
+/// rebinds the listener immediately; `allowed_origins` is only read when the listener (re)binds.
+#[derive(Clone)]
+pub struct McpServerConfig {
+    pub bind_addr: SocketAddr,
+    pub token: Option<String>,
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for McpServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: "127.0.0.1:3100".parse().unwrap(),
+            token: Some(generate_token()),
+            allowed_origins: vec!["http://localhost:3100".to_string()],
+        }
+    }
+}
+
+/// Generate a random bearer token. Used for the default config, by `load_or_generate_token`
+/// when no token has been persisted yet, and by `McpState::regenerate_token`.
+fn generate_token() -> String {
+    let mut rng = rand::thread_rng();
+    (0..32).map(|_| format!("{:x}", rng.gen_range(0..16u8))).collect()
+}
+
+/// Where the bearer token is persisted across app restarts, inside the app's own config dir.
+fn token_file_path(app: &AppHandle) -> Option<std::path::PathBuf> {
+    app.path().app_config_dir().ok().map(|dir| dir.join("mcp_token"))
+}
+
+/// The token written to disk by an earlier launch, if any.
+fn read_persisted_token(app: &AppHandle) -> Option<String> {
+    let path = token_file_path(app)?;
+    let token = std::fs::read_to_string(path).ok()?;
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+/// Write `token` to disk so it survives the next app restart. Best-effort: if the config dir
+/// can't be created or written to, the token still works for the current session. On Unix the
+/// file is restricted to the owner since it's a standing credential for full MCP access; on
+/// other platforms its permissions are left as created.
+fn persist_token(app: &AppHandle, token: &str) {
+    let Some(path) = token_file_path(app) else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    if std::fs::write(&path, token).is_ok() {
+        restrict_to_owner(&path);
+    }
+}
+
+#[cfg(unix)]
+fn restrict_to_owner(path: &std::path::Path) {
+    use std::os::unix::fs::PermissionsExt;
+    let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+}
+
+#[cfg(not(unix))]
+fn restrict_to_owner(_path: &std::path::Path) {}
+
+/// Read the token persisted by an earlier launch, or generate and persist a fresh one.
+fn load_or_generate_token(app: &AppHandle) -> String {
+    if let Some(token) = read_persisted_token(app) {
+        return token;
+    }
+    let token = generate_token();
+    persist_token(app, &token);
+    token
+}
 
 #[derive(Clone)]
 pub struct McpState {
     app: AppHandle,
+    pending: Arc<DashMap<Uuid, oneshot::Sender<Value>>>,
+    notifications: broadcast::Sender<Value>,
+    config: Arc<RwLock<McpServerConfig>>,
+    log: Arc<Mutex<VecDeque<McpEvent>>>,
+    /// Graceful-shutdown trigger for the currently running listener, so it can be torn down and
+    /// respawned on a new address without restarting the app. `None` before the first listener
+    /// is up.
+    listener_shutdown: Arc<Mutex<Option<oneshot::Sender<()>>>>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct McpResponse {
-    status: u16,
-    body: Value,
+/// One entry in the MCP audit log: a tool call or resource/prompt read, on receipt and again on
+/// completion, so the UI can show agents' activity as a reviewable trail.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct McpEvent {
+    id: Uuid,
+    timestamp_ms: u128,
+    direction: McpEventDirection,
+    method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u128>,
 }
 
-/// Start the MCP HTTP server on port 3100
-pub async fn start_mcp_server(app: AppHandle) -> Result<(), Box<dyn std::error::Error>> {
-    let state = McpState { app: app.clone() };
-
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-
-    let app_router = Router::new()
-        .route("/mcp/resources", get(list_resources))
-        .route("/mcp/resources/:uri", get(read_resource))
-        .route("/mcp/tools", get(list_tools))
-        .route("/mcp/tools/:name", post(call_tool))
-        .route("/mcp/prompts", get(list_prompts))
-        .route("/mcp/prompts/:name", get(get_prompt))
-        .layer(cors)
-        .with_state(Arc::new(state));
-
-    println!("[MCP Server] Starting HTTP server on http://localhost:3100");
-
-    // Spawn the server in a background task
-    tokio::spawn(async move {
-        let listener = tokio::net::TcpListener::bind("127.0.0.1:3100")
-            .await
-            .expect("Failed to bind MCP server port");
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum McpEventDirection {
+    Request,
+    Response,
+}
+
+impl McpEvent {
+    fn request(id: Uuid, method: &str, args: Option<Value>) -> Self {
+        Self {
+            id,
+            timestamp_ms: now_ms(),
+            direction: McpEventDirection::Request,
+            method: method.to_string(),
+            args,
+            status: None,
+            duration_ms: None,
+        }
+    }
+
+    fn response(id: Uuid, method: &str, status: u16, duration: Duration) -> Self {
+        Self {
+            id,
+            timestamp_ms: now_ms(),
+            direction: McpEventDirection::Response,
+            method: method.to_string(),
+            args: None,
+            status: Some(status),
+            duration_ms: Some(duration.as_millis()),
+        }
+    }
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+fn status_of(result: &Result<Value, (StatusCode, String)>) -> u16 {
+    match result {
+        Ok(_) => StatusCode::OK.as_u16(),
+        Err((status, _)) => status.as_u16(),
+    }
+}
+
+/// Event emitted on `mcp://request`; the frontend answers it by calling `mcp_respond` with the
+/// same `id`.
+#[derive(Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum McpFrontendRequest {
+    ReadResource { id: Uuid, uri: String },
+    CallTool { id: Uuid, name: String, args: Value },
+    GetPrompt { id: Uuid, name: String },
+    Screenshot {
+        id: Uuid,
+        #[serde(rename = "maxWidth")]
+        max_width: Option<u32>,
+        #[serde(rename = "maxHeight")]
+        max_height: Option<u32>,
+    },
+}
+
+/// A JSON-RPC 2.0 error, using the standard reserved codes where they apply.
+struct JsonRpcError {
+    code: i32,
+    message: String,
+}
+
+impl JsonRpcError {
+    fn method_not_found(method: &str) -> Self {
+        Self {
+            code: -32601,
+            message: format!("Method not found: {method}"),
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self {
+            code: -32602,
+            message: message.into(),
+        }
+    }
+
+    fn from_status((status, message): (StatusCode, String)) -> Self {
+        let code = if status == StatusCode::REQUEST_TIMEOUT {
+            -32001
+        } else if status == StatusCode::BAD_REQUEST {
+            -32602
+        } else {
+            -32603
+        };
+        Self { code, message }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonRpcErrorBody {
+    code: i32,
+    message: String,
+}
 
-        println!("[MCP Server] HTTP server listening on http://localhost:3100");
+#[derive(Deserialize)]
+struct JsonRpcRequest {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct JsonRpcResponse {
+    jsonrpc: &'static str,
+    id: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonRpcErrorBody>,
+}
+
+impl McpState {
+    fn new(app: AppHandle) -> Self {
+        let (notifications, _) = broadcast::channel(NOTIFICATION_CHANNEL_CAPACITY);
+        let config = McpServerConfig {
+            token: Some(load_or_generate_token(&app)),
+            ..McpServerConfig::default()
+        };
+        Self {
+            app,
+            pending: Arc::new(DashMap::new()),
+            notifications,
+            config: Arc::new(RwLock::new(config)),
+            log: Arc::new(Mutex::new(VecDeque::with_capacity(MCP_LOG_CAPACITY))),
+            listener_shutdown: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Record `event` in the ring buffer and push it to any connected `/sse` clients.
+    fn push_log_event(&self, event: McpEvent) {
+        {
+            let mut log = self.log.lock().unwrap();
+            if log.len() >= MCP_LOG_CAPACITY {
+                log.pop_front();
+            }
+            log.push_back(event.clone());
+        }
+        self.notify(serde_json::json!({ "type": "log", "event": event }));
+    }
+
+    /// The most recent audit log entries, oldest first.
+    pub fn recent_log(&self) -> Vec<McpEvent> {
+        self.log.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear_log(&self) {
+        self.log.lock().unwrap().clear();
+    }
+
+    pub async fn config(&self) -> McpServerConfig {
+        self.config.read().await.clone()
+    }
+
+    /// Change the bind address and rebind the listener to it immediately, shutting the previous
+    /// one down gracefully. If the new address can't be bound (already in use, no permission,
+    /// ...), the previous listener keeps running and `bind_addr` is left unchanged.
+    pub async fn set_bind_addr(&self, bind_addr: SocketAddr) -> std::io::Result<()> {
+        let previous = self.config.read().await.bind_addr;
+        self.config.write().await.bind_addr = bind_addr;
+        if let Err(e) = self.bind_listener().await {
+            self.config.write().await.bind_addr = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// (Re)build the router from the current config and bind it to `config.bind_addr`. Only once
+    /// the new listener is successfully bound do we shut down whatever listener was previously
+    /// running, so a failed rebind leaves the old one serving requests. Used both for the
+    /// initial startup in `start_mcp_server` and whenever `set_bind_addr` changes the address at
+    /// runtime.
+    async fn bind_listener(&self) -> std::io::Result<()> {
+        let config = self.config().await;
+        let shared_state = Arc::new(self.clone());
+
+        let origins: Vec<HeaderValue> = config
+            .allowed_origins
+            .iter()
+            .filter_map(|origin| origin.parse().ok())
+            .collect();
+        let cors = CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]);
+
+        let app_router = Router::new()
+            .route("/mcp", post(json_rpc))
+            .route("/sse", get(sse_handler))
+            .route("/mcp/resources", get(list_resources))
+            .route("/mcp/resources/:uri", get(read_resource))
+            .route("/mcp/tools", get(list_tools))
+            .route("/mcp/tools/:name", post(call_tool))
+            .route("/mcp/prompts", get(list_prompts))
+            .route("/mcp/prompts/:name", get(get_prompt))
+            .route("/mcp/log", get(get_log))
+            .layer(middleware::from_fn_with_state(
+                shared_state.clone(),
+                require_bearer_token,
+            ))
+            .layer(cors)
+            .with_state(shared_state);
+
+        let bind_addr = config.bind_addr;
+        let listener = tokio::net::TcpListener::bind(bind_addr).await?;
+
+        // The new listener is up; only now tear down whatever was running before.
+        if let Some(shutdown) = self.listener_shutdown.lock().unwrap().take() {
+            let _ = shutdown.send(());
+        }
+
+        println!("[MCP Server] HTTP server listening on http://{bind_addr}");
         println!("[MCP Server] Endpoints:");
+        println!("  - POST /mcp               (JSON-RPC 2.0 transport)");
+        println!("  - GET  /sse                (server-sent events transport)");
         println!("  - GET  /mcp/resources");
         println!("  - GET  /mcp/resources/:uri");
         println!("  - GET  /mcp/tools");
         println!("  - POST /mcp/tools/:name");
         println!("  - GET  /mcp/prompts");
         println!("  - GET  /mcp/prompts/:name");
+        println!("  - GET  /mcp/log");
 
-        axum::serve(listener, app_router)
-            .await
-            .expect("Failed to start MCP server");
-    });
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        *self.listener_shutdown.lock().unwrap() = Some(shutdown_tx);
+
+        tokio::spawn(async move {
+            axum::serve(listener, app_router)
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .await
+                .expect("Failed to start MCP server");
+        });
+
+        Ok(())
+    }
+
+    /// Replace the current bearer token with a freshly generated one, persist it, and return it.
+    pub async fn regenerate_token(&self) -> String {
+        let token = generate_token();
+        self.config.write().await.token = Some(token.clone());
+        persist_token(&self.app, &token);
+        token
+    }
+
+    /// Complete the oneshot for `id` with `result`. Called from the `mcp_respond` Tauri command.
+    /// Returns `false` if there was no matching pending request (already answered, timed out, or
+    /// an unknown id).
+    pub fn complete_pending(&self, id: Uuid, result: Value) -> bool {
+        match self.pending.remove(&id) {
+            Some((_, sender)) => sender.send(result).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Forward `request` to `window.__mcpServer` over the `mcp://request` event and await its
+    /// reply via the oneshot registered under `id`, instead of firing a `window.eval` that can
+    /// never hand a value back to Rust.
+    async fn ask_frontend(
+        &self,
+        id: Uuid,
+        request: McpFrontendRequest,
+    ) -> Result<Value, (StatusCode, String)> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(id, tx);
+
+        if let Err(e) = self.app.emit("mcp://request", &request) {
+            self.pending.remove(&id);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to notify frontend: {e}"),
+            ));
+        }
+
+        match tokio::time::timeout(FRONTEND_TIMEOUT, rx).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(_)) => {
+                self.pending.remove(&id);
+                Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Frontend dropped the request without responding".to_string(),
+                ))
+            }
+            Err(_) => {
+                self.pending.remove(&id);
+                Err((
+                    StatusCode::REQUEST_TIMEOUT,
+                    "Frontend did not respond in time".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Broadcast `payload` to every subscriber of the `/sse` transport. A no-op if nobody is
+    /// currently listening.
+    fn notify(&self, payload: Value) {
+        let _ = self.notifications.send(payload);
+    }
+
+    /// Ask the frontend to render the current stage to a PNG data URL (`data:image/png;base64,..`),
+    /// downscaled to fit within `max_width`/`max_height` if given. Used by both the
+    /// `project://screenshot` resource and the `capture_*_screenshot` commands.
+    pub(crate) async fn request_screenshot(
+        &self,
+        max_width: Option<u32>,
+        max_height: Option<u32>,
+    ) -> Result<String, (StatusCode, String)> {
+        let id = Uuid::new_v4();
+        let value = self
+            .ask_frontend(
+                id,
+                McpFrontendRequest::Screenshot {
+                    id,
+                    max_width,
+                    max_height,
+                },
+            )
+            .await?;
+
+        value
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Frontend returned a non-string screenshot payload".to_string(),
+                )
+            })
+    }
+}
+
+/// Start the MCP HTTP server using the bind address, bearer token and CORS origins currently
+/// held in `McpState::config` (defaults to `127.0.0.1:3100` with the token persisted by an
+/// earlier launch, or a freshly generated one if none was persisted).
+pub async fn start_mcp_server(app: AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    let state = McpState::new(app.clone());
+    app.manage(state.clone());
+
+    let bind_addr = state.config().await.bind_addr;
+    println!("[MCP Server] Starting HTTP server on http://{bind_addr}");
+    state.bind_listener().await?;
 
     Ok(())
 }
 
-/// List all available MCP resources
-async fn list_resources(State(state): State<Arc<McpState>>) -> Json<Value> {
-    let result = state
-        .app
-        .webview_windows()
-        .get("main")
-        .unwrap()
-        .eval("window.__mcpServer?.listResources()");
-
-    // Return a default response
-    Json(serde_json::json!({
+/// Reject requests whose `Authorization: Bearer <token>` header (or, for clients like
+/// `EventSource` that can't set custom headers, a `?token=` query parameter) doesn't match the
+/// configured token. A `None` token disables auth entirely, which is only meant for local
+/// development.
+async fn require_bearer_token(
+    State(state): State<Arc<McpState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(expected) = state.config().await.token else {
+        return Ok(next.run(request).await);
+    };
+
+    let header_token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let query_token = request
+        .uri()
+        .query()
+        .and_then(|query| url::form_urlencoded::parse(query.as_bytes()).find(|(k, _)| k == "token"))
+        .map(|(_, v)| v.into_owned());
+
+    let provided = header_token.map(str::to_string).or(query_token);
+
+    if provided.as_deref() == Some(expected.as_str()) {
+        Ok(next.run(request).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+fn resources_list_value() -> Value {
+    serde_json::json!({
         "resources": [
             {
                 "uri": "project://nodes",
@@ -103,50 +559,11 @@ async fn list_resources(State(state): State<Arc<McpState>>) -> Json<Value> {
                 "mimeType": "application/json"
             }
         ]
-    }))
-}
-
-/// Read a specific MCP resource
-async fn read_resource(
-    State(state): State<Arc<McpState>>,
-    Path(uri): Path<String>,
-) -> Result<Json<Value>, (StatusCode, String)> {
-    let decoded_uri = urlencoding::decode(&uri).unwrap_or_default().to_string();
-
-    // Use invoke to call frontend through Tauri command system
-    let window = state
-        .app
-        .webview_windows()
-        .get("main")
-        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Main window not found".to_string()))?;
-
-    // Evaluate JavaScript to get the resource
-    let script = format!(
-        r#"
-        (async () => {{
-            const result = await window.__mcpServer.readResource("{}");
-            return result;
-        }})()
-        "#,
-        decoded_uri.replace('"', "\\\"")
-    );
-
-    window
-        .eval(&script)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Eval error: {}", e)))?;
-
-    // Return a response - actual data will be shown in frontend
-    Ok(Json(serde_json::json!({
-        "status": "processing",
-        "uri": decoded_uri,
-        "message": "Check frontend console for full output"
-    })))
-}
+    })
 }
 
-/// List all available MCP tools
-async fn list_tools(State(_state): State<Arc<McpState>>) -> Json<Value> {
-    Json(serde_json::json!({
+fn tools_list_value() -> Value {
+    serde_json::json!({
         "tools": [
             {
                 "name": "addNode",
@@ -235,51 +652,65 @@ async fn list_tools(State(_state): State<Arc<McpState>>) -> Json<Value> {
                     },
                     "required": ["nodeId"]
                 }
+            },
+            {
+                "name": "batchMutations",
+                "description": "Execute an ordered list of the other tools as a single atomic, undo-able transaction. Either every operation applies or none do, and the whole batch collapses into one undo entry. An operation's `ref` can be targeted by `args` in later operations (e.g. an addNode's `ref` used as a later connectNodes sourceId) before the real node id exists.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "operations": {
+                            "type": "array",
+                            "description": "Ordered operations to apply atomically",
+                            "minItems": 1,
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "op": { "type": "string", "description": "Name of an existing tool, e.g. addNode" },
+                                    "args": { "type": "object", "description": "Arguments for the tool; a field may hold an earlier operation's `ref` instead of a real node id" },
+                                    "ref": { "type": "string", "description": "Temporary id this operation's result can be targeted by in later operations of the same batch" }
+                                },
+                                "required": ["op", "args"]
+                            }
+                        }
+                    },
+                    "required": ["operations"]
+                }
             }
         ]
-    }))
-}
-window = state
-        .app
-        .webview_windows()
-        .get("main")
-        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Main window not found".to_string()))?;
-
-    let script = format!(
-        r#"
-        (async () => {{
-            const result = await window.__mcpServer.callTool("{}", {});
-            console.log('[MCP] Tool result:', result);
-            return result;
-        }})()
-        "#,
-        name.replace('"', "\\\""),
-        args
-    );
-
-    window
-        .eval(&script)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Eval error: {}", e)))?;
-
-    Ok(Json(serde_json::json!({
-        "status": "success",
-        "tool": name,
-        "args": args,
-        "message": "Tool executed - check frontend console for result"
-    })))
-}ote: eval doesn't return values in Tauri
-    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "tool": name,
-        "args": args
-    })))
+    })
 }
 
-/// List all available MCP prompts
-async fn list_prompts(State(_state): State<Arc<McpState>>) -> Json<Value> {
-    Json(serde_json::json!({
+/// Reject malformed `batchMutations` payloads before bothering the frontend with them; every
+/// other tool is passed through untouched since its shape is validated by the frontend bridge.
+fn validate_call_tool_args(name: &str, args: &Value) -> Result<(), String> {
+    if name != "batchMutations" {
+        return Ok(());
+    }
+
+    let operations = args
+        .get("operations")
+        .and_then(Value::as_array)
+        .ok_or_else(|| "batchMutations requires an `operations` array".to_string())?;
+
+    if operations.is_empty() {
+        return Err("batchMutations requires at least one operation".to_string());
+    }
+
+    for (index, operation) in operations.iter().enumerate() {
+        if operation.get("op").and_then(Value::as_str).is_none() {
+            return Err(format!("operations[{index}] is missing a string `op`"));
+        }
+        if operation.get("args").is_none() {
+            return Err(format!("operations[{index}] is missing `args`"));
+        }
+    }
+
+    Ok(())
+}
+
+fn prompts_list_value() -> Value {
+    serde_json::json!({
         "prompts": [
             {
                 "name": "analyze-project",
@@ -290,7 +721,138 @@ async fn list_prompts(State(_state): State<Arc<McpState>>) -> Json<Value> {
                 "description": "Suggest ways to organize and improve the project structure"
             }
         ]
-    }))
+    })
+}
+
+async fn read_resource_value(
+    state: &McpState,
+    uri: String,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> Result<Value, (StatusCode, String)> {
+    let event_id = Uuid::new_v4();
+    let started = Instant::now();
+    state.push_log_event(McpEvent::request(
+        event_id,
+        "resources/read",
+        Some(serde_json::json!({ "uri": uri })),
+    ));
+
+    let result = if uri == "project://screenshot" {
+        crate::cmd::screenshot::screenshot_resource_value(state, max_width, max_height).await
+    } else {
+        let id = Uuid::new_v4();
+        state
+            .ask_frontend(id, McpFrontendRequest::ReadResource { id, uri })
+            .await
+    };
+
+    state.push_log_event(McpEvent::response(
+        event_id,
+        "resources/read",
+        status_of(&result),
+        started.elapsed(),
+    ));
+    result
+}
+
+async fn call_tool_value(
+    state: &McpState,
+    name: String,
+    args: Value,
+) -> Result<Value, (StatusCode, String)> {
+    let event_id = Uuid::new_v4();
+    let started = Instant::now();
+    state.push_log_event(McpEvent::request(
+        event_id,
+        &format!("tools/call:{name}"),
+        Some(args.clone()),
+    ));
+
+    let result = match validate_call_tool_args(&name, &args) {
+        Err(message) => Err((StatusCode::BAD_REQUEST, message)),
+        Ok(()) => {
+            let id = Uuid::new_v4();
+            state
+                .ask_frontend(id, McpFrontendRequest::CallTool { id, name: name.clone(), args })
+                .await
+        }
+    };
+
+    state.push_log_event(McpEvent::response(
+        event_id,
+        &format!("tools/call:{name}"),
+        status_of(&result),
+        started.elapsed(),
+    ));
+    result
+}
+
+async fn get_prompt_value(state: &McpState, name: String) -> Result<Value, (StatusCode, String)> {
+    let event_id = Uuid::new_v4();
+    let started = Instant::now();
+    state.push_log_event(McpEvent::request(
+        event_id,
+        &format!("prompts/get:{name}"),
+        None,
+    ));
+
+    let id = Uuid::new_v4();
+    let result = state
+        .ask_frontend(id, McpFrontendRequest::GetPrompt { id, name: name.clone() })
+        .await;
+
+    state.push_log_event(McpEvent::response(
+        event_id,
+        &format!("prompts/get:{name}"),
+        status_of(&result),
+        started.elapsed(),
+    ));
+    result
+}
+
+/// List all available MCP resources
+async fn list_resources(State(_state): State<Arc<McpState>>) -> Json<Value> {
+    Json(resources_list_value())
+}
+
+#[derive(Deserialize, Default)]
+struct ReadResourceQuery {
+    #[serde(rename = "maxWidth")]
+    max_width: Option<u32>,
+    #[serde(rename = "maxHeight")]
+    max_height: Option<u32>,
+}
+
+/// Read a specific MCP resource
+async fn read_resource(
+    State(state): State<Arc<McpState>>,
+    Path(uri): Path<String>,
+    Query(query): Query<ReadResourceQuery>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let decoded_uri = urlencoding::decode(&uri).unwrap_or_default().to_string();
+    Ok(Json(
+        read_resource_value(&state, decoded_uri, query.max_width, query.max_height).await?,
+    ))
+}
+
+/// List all available MCP tools
+async fn list_tools(State(_state): State<Arc<McpState>>) -> Json<Value> {
+    Json(tools_list_value())
+}
+
+/// Call an MCP tool
+async fn call_tool(
+    State(state): State<Arc<McpState>>,
+    Path(name): Path<String>,
+    Json(args): Json<Value>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    Ok(Json(call_tool_value(&state, name, args).await?))
+}
+
+/// List all available MCP prompts
+async fn list_prompts(State(_state): State<Arc<McpState>>) -> Json<Value> {
+    Json(prompts_list_value())
 }
 
 /// Get a specific MCP prompt
@@ -298,27 +860,108 @@ async fn get_prompt(
     State(state): State<Arc<McpState>>,
     Path(name): Path<String>,
 ) -> Result<Json<Value>, (StatusCode, String)> {
-    let script = format!(
-        r#"(async () => {{
-            try {{
-                const result = await window.__mcpServer.getPrompt("{}");
-                return JSON.stringify(result);
-            }} catch (err) {{
-                return JSON.stringify({{ error: err.message }});
-            }}
-        }})()"#,
-        name.replace("\"", "\\\"")
-    );
-
-    state
-        .app
-        .webview_windows()
-        .get("main")
-        .unwrap()
-        .eval(&script)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    Ok(Json(serde_json::json!({
-        "messages": []
-    })))
+    Ok(Json(get_prompt_value(&state, name).await?))
+}
+
+/// `POST /mcp` — the standard MCP JSON-RPC 2.0 HTTP transport, dispatching to the same logic the
+/// `/mcp/*` REST routes use.
+async fn json_rpc(
+    State(state): State<Arc<McpState>>,
+    Json(request): Json<JsonRpcRequest>,
+) -> Json<JsonRpcResponse> {
+    let id = request.id.clone();
+    let response = match dispatch_json_rpc(&state, &request.method, request.params).await {
+        Ok(result) => JsonRpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        },
+        Err(err) => JsonRpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(JsonRpcErrorBody {
+                code: err.code,
+                message: err.message,
+            }),
+        },
+    };
+    Json(response)
+}
+
+async fn dispatch_json_rpc(
+    state: &McpState,
+    method: &str,
+    params: Value,
+) -> Result<Value, JsonRpcError> {
+    match method {
+        "initialize" => Ok(serde_json::json!({
+            "protocolVersion": MCP_PROTOCOL_VERSION,
+            "capabilities": {
+                "resources": {},
+                "tools": {},
+                "prompts": {}
+            },
+            "serverInfo": {
+                "name": "project-graph",
+                "version": env!("CARGO_PKG_VERSION")
+            }
+        })),
+        "resources/list" => Ok(resources_list_value()),
+        "resources/read" => {
+            let uri = params
+                .get("uri")
+                .and_then(Value::as_str)
+                .ok_or_else(|| JsonRpcError::invalid_params("missing `uri`"))?
+                .to_string();
+            let max_width = params.get("maxWidth").and_then(Value::as_u64).map(|v| v as u32);
+            let max_height = params.get("maxHeight").and_then(Value::as_u64).map(|v| v as u32);
+            read_resource_value(state, uri, max_width, max_height)
+                .await
+                .map_err(JsonRpcError::from_status)
+        }
+        "tools/list" => Ok(tools_list_value()),
+        "tools/call" => {
+            let name = params
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| JsonRpcError::invalid_params("missing `name`"))?
+                .to_string();
+            let args = params.get("arguments").cloned().unwrap_or(Value::Null);
+            call_tool_value(state, name, args)
+                .await
+                .map_err(JsonRpcError::from_status)
+        }
+        "prompts/list" => Ok(prompts_list_value()),
+        "prompts/get" => {
+            let name = params
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| JsonRpcError::invalid_params("missing `name`"))?
+                .to_string();
+            get_prompt_value(state, name)
+                .await
+                .map_err(JsonRpcError::from_status)
+        }
+        other => Err(JsonRpcError::method_not_found(other)),
+    }
+}
+
+/// `GET /mcp/log` — the recent audit trail of tool calls and resource/prompt reads, oldest first.
+async fn get_log(State(state): State<Arc<McpState>>) -> Json<Value> {
+    Json(serde_json::json!({ "events": state.recent_log() }))
+}
+
+/// `GET /sse` — a long-lived `text/event-stream` connection that pushes each new audit log entry
+/// (`{"type": "log", "event": ..}`) as it is recorded. Tool results are not sent on this stream;
+/// they are only returned in the response to the call.
+async fn sse_handler(
+    State(state): State<Arc<McpState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(state.notifications.subscribe())
+        .filter_map(|message| message.ok())
+        .map(|payload| Ok(Event::default().data(payload.to_string())));
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
@@ -1,21 +1,247 @@
-use tauri::{AppHandle, Window};
+use crate::cmd::mcp_server::McpState;
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde_json::Value;
+use tauri::{AppHandle, State, Window};
 
-// Tauri v2 removed the screenshot() method
-// We'll need to implement this using JavaScript/HTML5 canvas
-// or return a placeholder for now
+/// Number of horizontal/vertical BlurHash components to encode. 4x3 is the density the format's
+/// reference implementation recommends for thumbnails: enough detail to be recognisable, small
+/// enough to stay a ~20-30 character string.
+const BLURHASH_COMPONENTS_X: u32 = 4;
+const BLURHASH_COMPONENTS_Y: u32 = 3;
+
+/// Ask the frontend (via `McpState::request_screenshot`) to render the current stage to a PNG
+/// data URL, then decode it into raw bytes. Tauri v2 dropped `Window::screenshot()`, so this is
+/// the only way to get pixels out of the webview.
+async fn capture_stage_png(
+    state: &McpState,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> Result<(String, Vec<u8>), String> {
+    let data_url = state
+        .request_screenshot(max_width, max_height)
+        .await
+        .map_err(|(_, message)| message)?;
+
+    let base64_data = data_url.split_once(',').map_or(data_url.as_str(), |(_, data)| data);
+    let bytes = STANDARD
+        .decode(base64_data)
+        .map_err(|e| format!("Invalid PNG data URL from frontend: {e}"))?;
+
+    Ok((base64_data.to_string(), bytes))
+}
 
 #[tauri::command]
-pub async fn capture_window_screenshot(_window: Window) -> Result<String, String> {
-    // TODO: Implement screenshot functionality for Tauri v2
-    // Options:
-    // 1. Use JavaScript canvas.toDataURL() from frontend
-    // 2. Use external screenshot library
-    // 3. Use platform-specific APIs
-    Err("Screenshot functionality not yet implemented for Tauri v2".to_string())
+pub async fn capture_window_screenshot(
+    _window: Window,
+    state: State<'_, McpState>,
+) -> Result<String, String> {
+    let (base64_png, _bytes) = capture_stage_png(&state, None, None).await?;
+    Ok(base64_png)
 }
 
 #[tauri::command]
-pub async fn capture_app_screenshot(_app: AppHandle) -> Result<String, String> {
-    // TODO: Implement screenshot functionality for Tauri v2
-    Err("Screenshot functionality not yet implemented for Tauri v2".to_string())
+pub async fn capture_app_screenshot(
+    _app: AppHandle,
+    state: State<'_, McpState>,
+) -> Result<String, String> {
+    let (base64_png, _bytes) = capture_stage_png(&state, None, None).await?;
+    Ok(base64_png)
+}
+
+/// Build the `project://screenshot` MCP resource: the rendered PNG as a base64 `blob`, plus a
+/// BlurHash placeholder so clients can show something before the full image has loaded.
+pub(crate) async fn screenshot_resource_value(
+    state: &McpState,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> Result<Value, (StatusCode, String)> {
+    let (base64_png, bytes) = capture_stage_png(state, max_width, max_height)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let image = image::load_from_memory(&bytes)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to decode screenshot PNG: {e}"),
+            )
+        })?
+        .to_rgba8();
+
+    let hash = blurhash::encode(
+        BLURHASH_COMPONENTS_X,
+        BLURHASH_COMPONENTS_Y,
+        image.width(),
+        image.height(),
+        image.as_raw(),
+    );
+
+    Ok(serde_json::json!({
+        "contents": [
+            {
+                "uri": "project://screenshot",
+                "mimeType": "image/png",
+                "blob": base64_png,
+                "blurhash": hash
+            }
+        ]
+    }))
+}
+
+/// A minimal BlurHash encoder (https://blurha.sh). Averages each image channel against a small
+/// grid of 2D cosine basis functions (a truncated DCT) and packs the resulting coefficients into
+/// a compact base83 string a client can decode into a blurred placeholder.
+mod blurhash {
+    const DIGIT_CHARACTERS: &[u8; 83] =
+        b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+    type Factor = [f64; 3];
+
+    pub fn encode(components_x: u32, components_y: u32, width: u32, height: u32, rgba: &[u8]) -> String {
+        let factors = compute_factors(components_x, components_y, width, height, rgba);
+        let (dc, ac) = factors.split_first().expect("at least the DC component");
+
+        let mut hash = String::new();
+        let size_flag = (components_x - 1) + (components_y - 1) * 9;
+        hash.push_str(&encode83(size_flag as u64, 1));
+
+        let maximum_value = if let Some(actual_max) = ac
+            .iter()
+            .flat_map(|factor| factor.iter())
+            .cloned()
+            .fold(None, |max, value| {
+                Some(max.map_or(value.abs(), |m: f64| m.max(value.abs())))
+            }) {
+            let quantised = ((actual_max * 166.0 - 0.5).floor() as i64).clamp(0, 82);
+            hash.push_str(&encode83(quantised as u64, 1));
+            (quantised as f64 + 1.0) / 166.0
+        } else {
+            hash.push_str(&encode83(0, 1));
+            1.0
+        };
+
+        hash.push_str(&encode83(encode_dc(dc), 4));
+        for factor in ac {
+            hash.push_str(&encode83(encode_ac(factor, maximum_value), 2));
+        }
+        hash
+    }
+
+    /// Average each channel of `rgba` against every (x, y) basis function up to `components_x` x
+    /// `components_y`, producing one linear-light RGB triple per basis function.
+    fn compute_factors(
+        components_x: u32,
+        components_y: u32,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Vec<Factor> {
+        let mut factors = Vec::with_capacity((components_x * components_y) as usize);
+        for y in 0..components_y {
+            for x in 0..components_x {
+                factors.push(multiply_basis_function(x, y, width, height, rgba));
+            }
+        }
+        factors
+    }
+
+    fn multiply_basis_function(
+        x_component: u32,
+        y_component: u32,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Factor {
+        let normalisation = if x_component == 0 && y_component == 0 {
+            1.0
+        } else {
+            2.0
+        };
+        let mut r = 0.0;
+        let mut g = 0.0;
+        let mut b = 0.0;
+
+        for y in 0..height {
+            for x in 0..width {
+                let basis = normalisation
+                    * (std::f64::consts::PI * x_component as f64 * x as f64 / width as f64).cos()
+                    * (std::f64::consts::PI * y_component as f64 * y as f64 / height as f64).cos();
+                let pixel = (y * width + x) as usize * 4;
+                r += basis * srgb_to_linear(rgba[pixel]);
+                g += basis * srgb_to_linear(rgba[pixel + 1]);
+                b += basis * srgb_to_linear(rgba[pixel + 2]);
+            }
+        }
+
+        let scale = 1.0 / (width * height) as f64;
+        [r * scale, g * scale, b * scale]
+    }
+
+    fn srgb_to_linear(value: u8) -> f64 {
+        let v = value as f64 / 255.0;
+        if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn linear_to_srgb(value: f64) -> u64 {
+        let v = value.clamp(0.0, 1.0);
+        let encoded = if v <= 0.0031308 {
+            v * 12.92 * 255.0 + 0.5
+        } else {
+            (1.055 * v.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5
+        };
+        encoded.round().clamp(0.0, 255.0) as u64
+    }
+
+    fn sign_pow(value: f64, exponent: f64) -> f64 {
+        value.signum() * value.abs().powf(exponent)
+    }
+
+    fn encode_dc(dc: &Factor) -> u64 {
+        let [r, g, b] = *dc;
+        (linear_to_srgb(r) << 16) + (linear_to_srgb(g) << 8) + linear_to_srgb(b)
+    }
+
+    fn encode_ac(ac: &Factor, maximum_value: f64) -> u64 {
+        let quantise = |value: f64| -> u64 {
+            (sign_pow(value / maximum_value, 0.5) * 9.0 + 9.5)
+                .floor()
+                .clamp(0.0, 18.0) as u64
+        };
+        let [r, g, b] = *ac;
+        quantise(r) * 19 * 19 + quantise(g) * 19 + quantise(b)
+    }
+
+    fn encode83(value: u64, length: u32) -> String {
+        (1..=length)
+            .map(|i| {
+                let digit = (value / 83u64.pow(length - i)) % 83;
+                DIGIT_CHARACTERS[digit as usize] as char
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::encode;
+
+        /// A solid-color image has no AC component, so the hash collapses to size flag +
+        /// quantised max (always `0` when there's nothing to quantise) + the 4-character DC term.
+        #[test]
+        fn encodes_a_solid_color_image() {
+            let rgba: Vec<u8> = (0..4).flat_map(|_| [128u8, 64, 32, 255]).collect();
+            assert_eq!(encode(1, 1, 2, 2, &rgba), "00E,p3");
+        }
+
+        /// A 2x1 black/white checkerboard with a 2x1 component grid exercises the AC branch too.
+        #[test]
+        fn encodes_a_checkerboard_image() {
+            let rgba: Vec<u8> = vec![0, 0, 0, 255, 255, 255, 255, 255];
+            assert_eq!(encode(2, 1, 2, 1, &rgba), "10Lqe9fQ");
+        }
+    }
 }